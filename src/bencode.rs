@@ -44,11 +44,15 @@ impl<'a> Node<'a> {
 
     pub fn as_dictionary(&self) -> Option<&HashMap<String, Node>> {
         if let Value::Dictionary(d) = &self.value {
-            Some(d) 
+            Some(d)
         } else {
             None
         }
     }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode(&self.value)
+    }
 }
 
 impl<'a> fmt::Display for Node<'a> {
@@ -109,7 +113,7 @@ pub enum ParseError {
 
 pub fn parse(content: &[u8]) -> Result<Node, ParseError> {
     let (value, parse_len) = parse_value(content)?;
-   
+
     if parse_len == content.len() {
         Ok(Node { value, unparsed: content })
     } else {
@@ -117,6 +121,51 @@ pub fn parse(content: &[u8]) -> Result<Node, ParseError> {
     }
 }
 
+// Like `parse`, but also returns the number of bytes consumed instead of
+// requiring the whole buffer to be a single Bencode value. Needed for
+// ut_metadata extension messages (BEP 9), which follow a Bencode dict with
+// raw, non-Bencode payload bytes.
+pub fn parse_prefix(content: &[u8]) -> Result<(Node, usize), ParseError> {
+    let (value, parse_len) = parse_value(content)?;
+
+    Ok((Node { value, unparsed: &content[..parse_len] }, parse_len))
+}
+
+// Canonical bencoding: integers as `i<n>e`, byte strings as `<len>:<bytes>`,
+// lists as `l...e`, and dictionaries as `d...e` with keys emitted in
+// lexicographically sorted raw-byte order, as required by the spec.
+pub fn encode(value: &Value) -> Vec<u8> {
+    match value {
+        Value::Integer(i) => format!("i{}e", i).into_bytes(),
+        Value::ByteString(bs) => {
+            let mut encoded = format!("{}:", bs.len()).into_bytes();
+            encoded.extend_from_slice(bs);
+            encoded
+        },
+        Value::List(list) => {
+            let mut encoded = vec![b'l'];
+            for node in list {
+                encoded.extend(encode(&node.value));
+            }
+            encoded.push(b'e');
+            encoded
+        },
+        Value::Dictionary(dict) => {
+            let mut entries: Vec<(&String, &Node)> = dict.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+
+            let mut encoded = vec![b'd'];
+            for (key, node) in entries {
+                encoded.extend(format!("{}:", key.len()).into_bytes());
+                encoded.extend(key.as_bytes());
+                encoded.extend(encode(&node.value));
+            }
+            encoded.push(b'e');
+            encoded
+        },
+    }
+}
+
 fn parse_value(content: &[u8]) -> Result<(Value, usize), ParseError> {
     if content.is_empty() {
         Err(ParseError::UnexpectedEndOfData)       
@@ -269,8 +318,55 @@ fn parse_dictionary(content: &[u8]) -> Option<Result<(HashMap<String, Node>, usi
 
         dict.insert(key, Node { value, unparsed: &content_from_value[..value_parse_len] });
         parse_len += key_parse_len + value_parse_len;
-    } 
+    }
 
     Some(Ok((dict, parse_len)))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trips(input: &[u8]) {
+        let node = parse(input).unwrap();
+        assert_eq!(encode(&node.value), input);
+    }
+
+    #[test]
+    fn round_trips_integer() {
+        assert_round_trips(b"i42e");
+    }
+
+    #[test]
+    fn round_trips_negative_integer() {
+        assert_round_trips(b"i-42e");
+    }
+
+    #[test]
+    fn round_trips_byte_string() {
+        assert_round_trips(b"4:spam");
+    }
+
+    #[test]
+    fn round_trips_list() {
+        assert_round_trips(b"l4:spam4:eggse");
+    }
+
+    #[test]
+    fn round_trips_dictionary_already_in_canonical_key_order() {
+        assert_round_trips(b"d3:cow3:moo4:spam4:eggse");
+    }
+
+    #[test]
+    fn encodes_dictionary_keys_in_sorted_order_regardless_of_insertion_order() {
+        let node = parse(b"d4:spam4:eggs3:cow3:mooe").unwrap();
+
+        assert_eq!(encode(&node.value), b"d3:cow3:moo4:spam4:eggse");
+    }
+
+    #[test]
+    fn round_trips_nested_structure() {
+        assert_round_trips(b"d8:announce22:http://tracker.example4:infod6:lengthi1024e4:name4:filee8:peer numi3ee");
+    }
+}
+
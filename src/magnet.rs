@@ -0,0 +1,135 @@
+use thiserror::Error;
+use url::Url;
+
+// Magnet links carry no info dictionary, just an info hash and a handful of
+// trackers/metadata to bootstrap from; the info dict itself has to be
+// fetched from a peer (see the `metadata` module).
+pub struct MagnetLink {
+    pub info_hash: [u8; 20],
+    pub trackers: Vec<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum MagnetError {
+    #[error("Not a valid magnet URI")]
+    InvalidUri,
+    #[error("Magnet URI is missing an xt=urn:btih:<hash> parameter")]
+    MissingInfoHash,
+    #[error("Magnet URI's info hash is not valid hex or base32")]
+    InvalidInfoHash,
+}
+
+pub fn parse(uri: &str) -> Result<MagnetLink, MagnetError> {
+    let url = Url::parse(uri).map_err(|_err| MagnetError::InvalidUri)?;
+
+    if url.scheme() != "magnet" {
+        return Err(MagnetError::InvalidUri);
+    }
+
+    let mut info_hash = None;
+    let mut trackers = Vec::new();
+    let mut name = None;
+
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "xt" => if let Some(btih) = value.strip_prefix("urn:btih:") {
+                info_hash = Some(decode_info_hash(btih)?);
+            },
+            "tr" => trackers.push(value.into_owned()),
+            "dn" => name = Some(value.into_owned()),
+            _ => {},
+        }
+    }
+
+    Ok(MagnetLink {
+        info_hash: info_hash.ok_or(MagnetError::MissingInfoHash)?,
+        trackers,
+        name,
+    })
+}
+
+fn decode_info_hash(btih: &str) -> Result<[u8; 20], MagnetError> {
+    let bytes = if btih.len() == 40 {
+        hex::decode(btih).map_err(|_err| MagnetError::InvalidInfoHash)?
+    } else {
+        base32::decode(base32::Alphabet::RFC4648 { padding: false }, btih)
+            .ok_or(MagnetError::InvalidInfoHash)?
+    };
+
+    bytes.try_into().map_err(|_bytes| MagnetError::InvalidInfoHash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO_HASH: [u8; 20] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+        0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13,
+    ];
+    const HEX_BTIH: &str = "000102030405060708090a0b0c0d0e0f10111213";
+    const BASE32_BTIH: &str = "AAAQEAYEAUDAOCAJBIFQYDIOB4IBCEQT";
+
+    #[test]
+    fn parses_hex_info_hash() {
+        let magnet = parse(&format!("magnet:?xt=urn:btih:{}", HEX_BTIH)).unwrap();
+
+        assert_eq!(magnet.info_hash, INFO_HASH);
+    }
+
+    #[test]
+    fn parses_base32_info_hash() {
+        let magnet = parse(&format!("magnet:?xt=urn:btih:{}", BASE32_BTIH)).unwrap();
+
+        assert_eq!(magnet.info_hash, INFO_HASH);
+    }
+
+    #[test]
+    fn missing_xt_is_an_error() {
+        let result = parse("magnet:?dn=some-name");
+
+        assert!(matches!(result, Err(MagnetError::MissingInfoHash)));
+    }
+
+    #[test]
+    fn collects_multiple_trackers_in_order() {
+        let magnet = parse(&format!(
+            "magnet:?xt=urn:btih:{}&tr=http://a.example/announce&tr=udp://b.example:80",
+            HEX_BTIH
+        )).unwrap();
+
+        assert_eq!(magnet.trackers, vec![
+            "http://a.example/announce",
+            "udp://b.example:80",
+        ]);
+    }
+
+    #[test]
+    fn no_trackers_is_an_empty_list_not_an_error() {
+        let magnet = parse(&format!("magnet:?xt=urn:btih:{}", HEX_BTIH)).unwrap();
+
+        assert!(magnet.trackers.is_empty());
+    }
+
+    #[test]
+    fn display_name_is_present_when_dn_is_given() {
+        let magnet = parse(&format!("magnet:?xt=urn:btih:{}&dn=Some+Name", HEX_BTIH)).unwrap();
+
+        assert_eq!(magnet.name.as_deref(), Some("Some Name"));
+    }
+
+    #[test]
+    fn display_name_is_absent_when_dn_is_missing() {
+        let magnet = parse(&format!("magnet:?xt=urn:btih:{}", HEX_BTIH)).unwrap();
+
+        assert_eq!(magnet.name, None);
+    }
+
+    #[test]
+    fn non_magnet_scheme_is_an_error() {
+        let result = parse("http://example.com");
+
+        assert!(matches!(result, Err(MagnetError::InvalidUri)));
+    }
+}
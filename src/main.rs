@@ -1,6 +1,12 @@
 mod torrent_file;
 mod tracker;
 mod bencode;
+mod peer;
+mod piece;
+mod magnet;
+mod metadata;
+
+use rand::seq::SliceRandom;
 
 #[tokio::main]
 async fn main() {
@@ -24,6 +30,129 @@ async fn main() {
         left: torrent_length,
         event: tracker::AnnounceEvent::Started
     };
-    tracker::announce(&torrent_info.announce, &request).await.unwrap();
+
+    let response = announce_with_fallback(&torrent_info.announce_list, &request).await.unwrap();
+
+    if let Some(peer) = response.peers.first() {
+        download_first_piece(peer, &torrent_info.info, &request.info_hash, PEER_ID).await;
+    }
+
+    // A magnet link carries no info dict, so the same peer-wire-protocol and
+    // ut_metadata machinery above is also the only way to bootstrap one.
+    const MAGNET_URI: &str = "magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a&dn=ubuntu-23.10-beta-live-server-amd64.iso&tr=https://torrent.ubuntu.com/announce";
+
+    download_info_from_magnet(MAGNET_URI, PEER_ID, PORT).await;
+}
+
+// Connects to a single peer and downloads+verifies piece 0, to exercise the
+// peer wire protocol end to end; a real client would do this for every piece
+// across every connected peer.
+async fn download_first_piece(peer: &tracker::Peer, info: &torrent_file::Info, info_hash: &[u8; 20], peer_id: &str) {
+    let mut connection = match peer::PeerConnection::connect(peer, info_hash, peer_id).await {
+        Ok(connection) => connection,
+        Err(err) => {
+            println!("Failed to connect to peer {}:{}: {}", peer.ip, peer.port, err);
+            return;
+        }
+    };
+
+    let piece_length = piece::piece_len(info, 0) as u32;
+
+    println!(
+        "Piece 0 is {} bytes across {} blocks (first block {} bytes).",
+        piece_length, piece::block_count(info, 0), piece::block_len(info, 0, 0),
+    );
+
+    let data = match connection.download_piece(0, piece_length).await {
+        Ok(data) => data,
+        Err(err) => {
+            println!("Failed to download piece 0: {}", err);
+            return;
+        }
+    };
+
+    let mut piece_manager = piece::PieceManager::new(info);
+    match piece_manager.add_block(0, 0, data) {
+        Some(_) => println!("Downloaded and verified piece 0. Completed pieces: {:?}", piece_manager.completed()),
+        None => println!("Piece 0 failed SHA-1 verification."),
+    }
+}
+
+// Bootstraps straight from a magnet link: announce to its trackers, connect
+// to a peer, and fetch the info dict via the ut_metadata extension (BEP 9).
+async fn download_info_from_magnet(magnet_uri: &str, peer_id: &str, port: u16) {
+    let magnet = match magnet::parse(magnet_uri) {
+        Ok(magnet) => magnet,
+        Err(err) => {
+            println!("Failed to parse magnet link: {}", err);
+            return;
+        }
+    };
+
+    let params = tracker::AnnounceParams {
+        info_hash: magnet.info_hash,
+        peer_id: String::from(peer_id),
+        port,
+        uploaded: 0,
+        downloaded: 0,
+        left: 0,
+        event: tracker::AnnounceEvent::Started,
+    };
+
+    let response = match announce_with_fallback(&[magnet.trackers], &params).await {
+        Ok(response) => response,
+        Err(err) => {
+            println!("Failed to announce from magnet trackers: {}", err);
+            return;
+        }
+    };
+
+    let peer = match response.peers.first() {
+        Some(peer) => peer,
+        None => {
+            println!("Tracker returned no peers for magnet link.");
+            return;
+        }
+    };
+
+    let mut connection = match peer::PeerConnection::connect(peer, &magnet.info_hash, peer_id).await {
+        Ok(connection) => connection,
+        Err(err) => {
+            println!("Failed to connect to peer {}:{}: {}", peer.ip, peer.port, err);
+            return;
+        }
+    };
+
+    match metadata::fetch_info(&mut connection, &magnet.info_hash).await {
+        Ok(info) => println!("Fetched info dict via magnet link: {:?}", info.name),
+        Err(err) => println!("Failed to fetch metadata: {}", err),
+    }
+}
+
+// Tries every tracker in announce-list order (BEP 12), shuffling the URLs
+// within each tier per the spec, and falls through to the next tracker when
+// one fails instead of giving up on the whole torrent.
+async fn announce_with_fallback(
+    announce_list: &[Vec<String>],
+    params: &tracker::AnnounceParams,
+) -> Result<tracker::AnnounceResponse, tracker::AnnounceError> {
+    let mut last_error = None;
+
+    for tier in announce_list {
+        let mut urls = tier.clone();
+        urls.shuffle(&mut rand::thread_rng());
+
+        for url in urls {
+            match tracker::announce(&url, params).await {
+                Ok(response) => return Ok(response),
+                Err(err) => {
+                    println!("Failed to announce to {}: {}", url, err);
+                    last_error = Some(err);
+                }
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or(tracker::AnnounceError::NoTrackers))
 }
 
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+use crate::bencode::{self, Node, Value};
+use crate::peer::{PeerConnection, PeerError};
+use crate::torrent_file::{self, Info, ParseError as TorrentFileParseError};
+
+const EXTENDED_HANDSHAKE_ID: u8 = 0;
+// Arbitrary id we advertise for ut_metadata in our own extended handshake;
+// peers tag messages sent to us with whatever id we advertised here.
+const UT_METADATA_LOCAL_ID: u8 = 1;
+
+const UT_METADATA_MSG_REQUEST: i64 = 0;
+const UT_METADATA_MSG_DATA: i64 = 1;
+const UT_METADATA_MSG_REJECT: i64 = 2;
+
+#[derive(Error, Debug)]
+pub enum MetadataError {
+    #[error("{0}")]
+    Peer(#[from] PeerError),
+    #[error("Peer's response contains invalid bencode: {0}")]
+    InvalidBencode(#[from] bencode::ParseError),
+    #[error("Peer does not support the ut_metadata extension")]
+    UnsupportedExtension,
+    #[error("Peer's extended handshake is missing field {0}")]
+    MissingField(&'static str),
+    #[error("Peer rejected our request for metadata piece {0}")]
+    Rejected(usize),
+    #[error("Assembled metadata does not match the requested info hash")]
+    HashMismatch,
+    #[error("Failed to parse assembled metadata: {0}")]
+    InvalidMetadata(#[from] TorrentFileParseError),
+}
+
+// Fetches the info dictionary from a peer that has already completed the
+// BitTorrent handshake, via the ut_metadata extension (BEP 9, built on the
+// BEP 10 extension protocol). This is how a magnet link (which carries no
+// info dict of its own) bootstraps into the same `Info` that `torrent_file`
+// produces from a .torrent file.
+pub async fn fetch_info(connection: &mut PeerConnection, info_hash: &[u8; 20]) -> Result<Info, MetadataError> {
+    connection.send_extended(EXTENDED_HANDSHAKE_ID, &encode_handshake()).await?;
+
+    let (ut_metadata_remote_id, metadata_size) = loop {
+        let (id, payload) = connection.receive_extended().await?;
+        if id == EXTENDED_HANDSHAKE_ID {
+            break parse_handshake(&payload)?;
+        }
+    };
+
+    let piece_count = crate::piece::block_count_for_len(metadata_size);
+    let mut pieces: Vec<Option<Vec<u8>>> = vec![None; piece_count];
+
+    for piece in 0..piece_count {
+        connection.send_extended(ut_metadata_remote_id, &encode_request(piece)).await?;
+
+        loop {
+            let (id, payload) = connection.receive_extended().await?;
+            if id != UT_METADATA_LOCAL_ID {
+                continue;
+            }
+
+            let (msg_type, response_piece, data) = parse_piece_message(&payload)?;
+            if response_piece != piece {
+                continue;
+            }
+
+            match msg_type {
+                UT_METADATA_MSG_DATA => {
+                    pieces[piece] = Some(data);
+                    break;
+                },
+                UT_METADATA_MSG_REJECT => return Err(MetadataError::Rejected(piece)),
+                _ => continue,
+            }
+        }
+    }
+
+    let metadata: Vec<u8> = pieces.into_iter()
+        .collect::<Option<Vec<Vec<u8>>>>()
+        .ok_or(MetadataError::UnsupportedExtension)?
+        .concat();
+
+    let mut hasher = Sha1::new();
+    hasher.update(&metadata);
+    if hasher.finalize().as_slice() != info_hash {
+        return Err(MetadataError::HashMismatch);
+    }
+
+    Ok(torrent_file::parse_info(&metadata)?)
+}
+
+fn bencode_dict(entries: Vec<(&str, Value<'static>)>) -> Value<'static> {
+    let map = entries.into_iter()
+        .map(|(key, value)| (key.to_string(), Node { value, unparsed: &[] }))
+        .collect::<HashMap<String, Node<'static>>>();
+
+    Value::Dictionary(map)
+}
+
+fn encode_handshake() -> Vec<u8> {
+    let m = bencode_dict(vec![
+        ("ut_metadata", Value::Integer(UT_METADATA_LOCAL_ID as i64)),
+    ]);
+
+    bencode::encode(&bencode_dict(vec![("m", m)]))
+}
+
+fn parse_handshake(payload: &[u8]) -> Result<(u8, usize), MetadataError> {
+    let node = bencode::parse(payload)?;
+    let dict = node.as_dictionary().ok_or(MetadataError::MissingField("handshake"))?;
+
+    let ut_metadata_id = dict.get("m")
+        .and_then(|m| m.as_dictionary())
+        .and_then(|m| m.get("ut_metadata"))
+        .and_then(|id| id.as_integer())
+        .ok_or(MetadataError::UnsupportedExtension)? as u8;
+
+    let metadata_size = dict.get("metadata_size")
+        .and_then(|size| size.as_integer())
+        .ok_or(MetadataError::MissingField("metadata_size"))? as usize;
+
+    Ok((ut_metadata_id, metadata_size))
+}
+
+fn encode_request(piece: usize) -> Vec<u8> {
+    let dict = bencode_dict(vec![
+        ("msg_type", Value::Integer(UT_METADATA_MSG_REQUEST)),
+        ("piece", Value::Integer(piece as i64)),
+    ]);
+
+    bencode::encode(&dict)
+}
+
+// The ut_metadata piece message is a Bencode dict immediately followed by
+// the raw metadata bytes for that piece (for `msg_type == data` only).
+fn parse_piece_message(payload: &[u8]) -> Result<(i64, usize, Vec<u8>), MetadataError> {
+    let (node, parse_len) = bencode::parse_prefix(payload)?;
+    let dict = node.as_dictionary().ok_or(MetadataError::MissingField("piece message"))?;
+
+    let msg_type = dict.get("msg_type")
+        .and_then(|msg_type| msg_type.as_integer())
+        .ok_or(MetadataError::MissingField("msg_type"))?;
+
+    let piece = dict.get("piece")
+        .and_then(|piece| piece.as_integer())
+        .ok_or(MetadataError::MissingField("piece"))? as usize;
+
+    Ok((msg_type, piece, payload[parse_len..].to_vec()))
+}
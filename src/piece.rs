@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use sha1::{Digest, Sha1};
+
+use crate::torrent_file::Info;
+
+pub const BLOCK_SIZE: usize = 1 << 14; // 16 KiB
+
+// Every piece is `info.piece_length` bytes except the last, which is
+// whatever remains of the torrent's total length.
+pub fn piece_len(info: &Info, index: usize) -> usize {
+    let total_length: usize = info.files.iter().map(|file| file.length).sum();
+    let last_index = info.pieces.len() - 1;
+    let remainder = total_length % info.piece_length;
+
+    if index == last_index && remainder != 0 {
+        remainder
+    } else {
+        info.piece_length
+    }
+}
+
+// Shared by any caller chunking a byte length into `BLOCK_SIZE` blocks, not
+// just torrent pieces (e.g. `metadata`'s ut_metadata piece count), so the
+// div-ceil arithmetic has a single home.
+pub fn block_count_for_len(len: usize) -> usize {
+    len.div_ceil(BLOCK_SIZE)
+}
+
+pub fn block_count(info: &Info, index: usize) -> usize {
+    block_count_for_len(piece_len(info, index))
+}
+
+pub fn block_len(info: &Info, index: usize, block: usize) -> usize {
+    let begin = block * BLOCK_SIZE;
+
+    BLOCK_SIZE.min(piece_len(info, index) - begin)
+}
+
+pub fn verify(info: &Info, index: usize, data: &[u8]) -> bool {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+
+    hasher.finalize().as_slice() == info.pieces[index].as_slice()
+}
+
+// Buffers a single piece's blocks by their `begin` offset until every block
+// has arrived, then hands back the assembled piece.
+struct PieceState {
+    blocks: HashMap<u32, Vec<u8>>,
+    piece_len: usize,
+}
+
+impl PieceState {
+    fn new(piece_len: usize) -> PieceState {
+        PieceState { blocks: HashMap::new(), piece_len }
+    }
+
+    // Drops a block that would run past the piece's end rather than
+    // buffering it, so a single out-of-range `begin` can't permanently
+    // wedge this piece (assemble() would otherwise never see all blocks
+    // tile cleanly again).
+    fn add_block(&mut self, begin: u32, data: Vec<u8>) {
+        let in_range = (begin as usize)
+            .checked_add(data.len())
+            .is_some_and(|end| end <= self.piece_len);
+
+        if in_range {
+            self.blocks.insert(begin, data);
+        }
+    }
+
+    // Summing received lengths isn't enough to know the piece is complete:
+    // overlapping or out-of-range blocks could sum to `piece_len` while
+    // leaving gaps (or indexing out of bounds) once assembled. Instead,
+    // sort blocks by `begin` and require them to tile `0..piece_len`
+    // exactly, with no gap or overlap between consecutive blocks.
+    fn assemble(&self) -> Option<Vec<u8>> {
+        let mut blocks: Vec<(usize, &Vec<u8>)> = self.blocks.iter()
+            .map(|(&begin, data)| (begin as usize, data))
+            .collect();
+        blocks.sort_by_key(|&(begin, _)| begin);
+
+        let mut expected_begin = 0;
+        for &(begin, data) in &blocks {
+            if begin != expected_begin {
+                return None;
+            }
+            expected_begin += data.len();
+        }
+
+        if expected_begin != self.piece_len {
+            return None;
+        }
+
+        let mut buffer = vec![0u8; self.piece_len];
+
+        for (begin, data) in blocks {
+            buffer[begin..begin + data.len()].copy_from_slice(data);
+        }
+
+        Some(buffer)
+    }
+}
+
+// Tracks which pieces of a torrent are complete, verifying each one against
+// its SHA-1 hash as soon as all of its blocks have arrived.
+pub struct PieceManager<'a> {
+    info: &'a Info,
+    in_progress: HashMap<usize, PieceState>,
+    completed: Vec<bool>,
+}
+
+impl<'a> PieceManager<'a> {
+    pub fn new(info: &'a Info) -> PieceManager<'a> {
+        PieceManager {
+            info,
+            in_progress: HashMap::new(),
+            completed: vec![false; info.pieces.len()],
+        }
+    }
+
+    pub fn completed(&self) -> &[bool] {
+        &self.completed
+    }
+
+    // Buffers an incoming block. Once every block for its piece has arrived
+    // the assembled piece is verified against its hash; verified data is
+    // returned and the piece is marked complete, corrupt data is dropped so
+    // the caller can re-request it.
+    pub fn add_block(&mut self, index: usize, begin: u32, data: Vec<u8>) -> Option<Vec<u8>> {
+        if self.completed[index] {
+            return None;
+        }
+
+        let piece_len = piece_len(self.info, index);
+        let state = self.in_progress
+            .entry(index)
+            .or_insert_with(|| PieceState::new(piece_len));
+
+        state.add_block(begin, data);
+
+        let assembled = state.assemble()?;
+        self.in_progress.remove(&index);
+
+        if verify(self.info, index, &assembled) {
+            self.completed[index] = true;
+            Some(assembled)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::torrent_file::File;
+
+    // One full-length piece plus a short final piece: total_length is not a
+    // multiple of piece_length.
+    fn test_info() -> Info {
+        let piece_a = vec![0u8; BLOCK_SIZE * 2];
+        let piece_b = vec![1u8; 10];
+
+        let hash = |data: &[u8]| {
+            let mut hasher = Sha1::new();
+            hasher.update(data);
+            hasher.finalize().to_vec()
+        };
+
+        Info {
+            hash: [0u8; 20],
+            name: None,
+            piece_length: BLOCK_SIZE * 2,
+            pieces: vec![hash(&piece_a), hash(&piece_b)],
+            files: vec![File { path: vec![String::from("f")], length: piece_a.len() + piece_b.len() }],
+        }
+    }
+
+    #[test]
+    fn short_final_piece_is_shorter_than_piece_length() {
+        let info = test_info();
+
+        assert_eq!(piece_len(&info, 0), BLOCK_SIZE * 2);
+        assert_eq!(piece_len(&info, 1), 10);
+    }
+
+    #[test]
+    fn short_final_piece_has_one_short_block() {
+        let info = test_info();
+
+        assert_eq!(block_count(&info, 1), 1);
+        assert_eq!(block_len(&info, 1, 0), 10);
+    }
+
+    #[test]
+    fn full_piece_has_exactly_two_full_blocks() {
+        let info = test_info();
+
+        assert_eq!(block_count(&info, 0), 2);
+        assert_eq!(block_len(&info, 0, 0), BLOCK_SIZE);
+        assert_eq!(block_len(&info, 0, 1), BLOCK_SIZE);
+    }
+
+    #[test]
+    fn add_block_returns_assembled_piece_once_verified() {
+        let info = test_info();
+        let mut manager = PieceManager::new(&info);
+
+        let data = vec![1u8; 10];
+        let assembled = manager.add_block(1, 0, data.clone());
+
+        assert_eq!(assembled, Some(data));
+        assert_eq!(manager.completed(), &[false, true]);
+    }
+
+    #[test]
+    fn add_block_rejects_data_that_does_not_match_the_piece_hash() {
+        let info = test_info();
+        let mut manager = PieceManager::new(&info);
+
+        let corrupt = vec![0xffu8; 10];
+        let assembled = manager.add_block(1, 0, corrupt);
+
+        assert_eq!(assembled, None);
+        assert_eq!(manager.completed(), &[false, false]);
+    }
+
+    #[test]
+    fn add_block_ignores_an_out_of_range_begin_instead_of_completing() {
+        let info = test_info();
+        let mut manager = PieceManager::new(&info);
+
+        // `begin` equals the piece length, so this block would index past
+        // the end of the buffer if assembled blindly; its length still
+        // happens to sum to piece_len on its own.
+        let out_of_range = manager.add_block(1, 10, vec![1u8; 10]);
+        assert_eq!(out_of_range, None);
+
+        let assembled = manager.add_block(1, 0, vec![1u8; 10]);
+        assert_eq!(assembled, Some(vec![1u8; 10]));
+    }
+
+    #[test]
+    fn add_block_ignores_overlapping_blocks_instead_of_completing() {
+        let info = test_info();
+        let mut manager = PieceManager::new(&info);
+
+        // Two 5-byte blocks whose lengths sum to piece_len (10) but
+        // overlap (begin 0..5 and 3..8), leaving bytes 8..10 uncovered.
+        manager.add_block(1, 0, vec![1u8; 5]);
+        let assembled = manager.add_block(1, 3, vec![1u8; 5]);
+
+        assert_eq!(assembled, None);
+        assert_eq!(manager.completed(), &[false, false]);
+    }
+}
@@ -1,7 +1,7 @@
 use std::{fs, io};
+use std::collections::HashMap;
 use thiserror::Error;
 use sha1::{Sha1, Digest};
-use hex;
 
 use crate::bencode;
 
@@ -11,7 +11,7 @@ pub struct File {
 }
 
 pub struct Info {
-    pub hash: String,
+    pub hash: [u8; 20],
     pub name: Option<String>,
     pub piece_length: usize,
     pub pieces: Vec<Vec<u8>>,
@@ -20,6 +20,11 @@ pub struct Info {
 
 pub struct TorrentInfo {
     pub announce: String,
+    // Multi-tracker tiers (BEP 12). Tier 0 is tried first; trackers within a
+    // tier are tried in (shuffled) order before falling through to the next
+    // tier. Falls back to a single tier containing `announce` when the
+    // torrent has no `announce-list`.
+    pub announce_list: Vec<Vec<String>>,
     pub info: Info,
 }
 
@@ -39,7 +44,7 @@ pub fn parse(filepath: &str) -> Result<TorrentInfo, ParseError> {
     let bencode_content = fs::read(filepath)?;
     
     let root = bencode::parse(&bencode_content)
-        .ok_or(ParseError::InvalidBencode)?;
+        .map_err(|_err| ParseError::InvalidBencode)?;
 
     let root_dict = root.as_dictionary()
         .ok_or(ParseError::MissingField("root"))?;
@@ -49,13 +54,55 @@ pub fn parse(filepath: &str) -> Result<TorrentInfo, ParseError> {
         .ok_or(ParseError::MissingField("announce"))?
         .to_string();
 
+    let announce_list = parse_announce_list(root_dict, &announce);
+
     let info_value = root_dict.get("info")
         .ok_or(ParseError::MissingField("info"))?;
 
+    let info = build_info(info_value)?;
+
+    Ok(TorrentInfo {
+        announce,
+        announce_list,
+        info,
+    })
+}
+
+// Falls back to a single tier containing `announce` only when there's no
+// `announce-list` field at all; a tier that's present but empty (e.g. `[[]]`)
+// is passed through as-is rather than triggering the fallback.
+fn parse_announce_list(root_dict: &HashMap<String, bencode::Node>, announce: &str) -> Vec<Vec<String>> {
+    root_dict.get("announce-list")
+        .and_then(|announce_list| announce_list.as_list())
+        .map(|tiers| {
+            tiers.iter()
+                .filter_map(|tier| tier.as_list())
+                .map(|tier| {
+                    tier.iter()
+                        .filter_map(|url| url.as_str().map(|url| url.to_string()))
+                        .collect::<Vec<String>>()
+                })
+                .collect::<Vec<Vec<String>>>()
+        })
+        .unwrap_or_else(|| vec![vec![announce.to_string()]])
+}
+
+// Parses a bare info dictionary (as opposed to a full .torrent file) into an
+// `Info`. Used to build the same `Info` that `parse` produces once metadata
+// has been fetched from a peer over the extension protocol (BEP 9), since
+// magnet links carry no info dictionary of their own.
+pub fn parse_info(content: &[u8]) -> Result<Info, ParseError> {
+    let info_value = bencode::parse(content)
+        .map_err(|_err| ParseError::InvalidBencode)?;
+
+    build_info(&info_value)
+}
+
+fn build_info(info_value: &bencode::Node) -> Result<Info, ParseError> {
     // TODO Is recreating the hasher for each announce ok?
     let mut hasher = Sha1::new();
     hasher.update(info_value.unparsed);
-    let hash = hex::encode(hasher.finalize().as_slice());
+    let hash: [u8; 20] = hasher.finalize().into();
 
     let info_dict = info_value.as_dictionary()
         .ok_or(ParseError::MissingField("info"))?;
@@ -86,11 +133,11 @@ pub fn parse(filepath: &str) -> Result<TorrentInfo, ParseError> {
         pieces_left = &pieces_left[PIECE_SIZE..];
     }
 
-    let files = 
+    let files =
         if let Some(length) =
             info_dict.get("length")
                 .and_then(|length| length.as_integer()) {
-            
+
             vec![File {
                 path: vec![
                     name.as_ref()
@@ -122,15 +169,51 @@ pub fn parse(filepath: &str) -> Result<TorrentInfo, ParseError> {
                 .collect::<Result<Vec<File>, ParseError>>()?
         };
 
-    Ok(TorrentInfo {
-        announce,
-        info: Info {
-            hash, 
-            name,
-            piece_length,
-            pieces,
-            files,
-        },
+    Ok(Info {
+        hash,
+        name,
+        piece_length,
+        pieces,
+        files,
     })
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANNOUNCE: &str = "http://tracker.example";
+
+    #[test]
+    fn parse_announce_list_falls_back_to_announce_when_there_is_no_announce_list() {
+        let root = bencode::parse(b"d8:announce22:http://tracker.examplee").unwrap();
+        let root_dict = root.as_dictionary().unwrap();
+
+        assert_eq!(parse_announce_list(root_dict, ANNOUNCE), vec![vec![ANNOUNCE.to_string()]]);
+    }
+
+    #[test]
+    fn parse_announce_list_passes_through_a_tier_that_is_present_but_empty() {
+        let root = bencode::parse(b"d8:announce22:http://tracker.example13:announce-listlleee").unwrap();
+        let root_dict = root.as_dictionary().unwrap();
+
+        assert_eq!(parse_announce_list(root_dict, ANNOUNCE), vec![Vec::<String>::new()]);
+    }
+
+    #[test]
+    fn parse_announce_list_collects_trackers_from_every_tier() {
+        let root = bencode::parse(
+            b"d8:announce22:http://tracker.example13:announce-listll18:http://a.example/1el18:http://b.example/2eee"
+        ).unwrap();
+        let root_dict = root.as_dictionary().unwrap();
+
+        assert_eq!(
+            parse_announce_list(root_dict, ANNOUNCE),
+            vec![
+                vec![String::from("http://a.example/1")],
+                vec![String::from("http://b.example/2")],
+            ],
+        );
+    }
+}
+
@@ -1,11 +1,28 @@
-use std::{fmt, str, time::Duration};
+use std::{fmt, io, str, time::Duration};
+use rand::Rng;
 use reqwest;
 use thiserror::Error;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
 use urlencoding;
-use url::form_urlencoded;
+use url::{form_urlencoded, Url};
 
 use crate::bencode;
 
+// BEP 15 (UDP tracker protocol) magic constant identifying the protocol.
+const UDP_PROTOCOL_ID: u64 = 0x41727101980;
+const UDP_ACTION_CONNECT: u32 = 0;
+const UDP_ACTION_ANNOUNCE: u32 = 1;
+const UDP_ACTION_ERROR: u32 = 3;
+// Retransmit schedule from BEP 15: 15 * 2^n seconds, giving up after 8 tries.
+const UDP_MAX_RETRIES: u32 = 8;
+const UDP_BASE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Compact peer list (BEP 23): 6 bytes per peer, 4-byte big-endian IPv4
+// followed by a 2-byte big-endian port. Used by both the HTTP tracker's
+// "peers" byte string and the UDP tracker's announce response.
+const COMPACT_PEER_SIZE: usize = 6;
+
 pub enum AnnounceEvent {
     Started,
     Completed,
@@ -32,15 +49,16 @@ pub struct AnnounceParams {
     pub event: AnnounceEvent,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Peer {
-    id: String,
-    ip: String,
-    port: u16,
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
 }
 
 pub struct AnnounceResponse {
-    interval: Duration,
-    peers: Vec<Peer>
+    pub interval: Duration,
+    pub peers: Vec<Peer>
 }
 
 #[derive(Error, Debug)]
@@ -55,9 +73,34 @@ pub enum AnnounceError {
     NegativeInterval,
     #[error("Tracker responded with error: {0}")]
     ErrorResponse(String),
+    #[error("Response peers byte string length is not a multiple of 6")]
+    InvalidPeers,
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("Unsupported announce URL scheme {0}")]
+    UnsupportedScheme(String),
+    #[error("Tracker did not respond after {0} retries")]
+    Timeout(u32),
+    #[error("Unexpected UDP response length")]
+    InvalidUdpResponse,
+    #[error("UDP response transaction id does not match the request")]
+    TransactionIdMismatch,
+    #[error("No tracker URLs available to announce to")]
+    NoTrackers,
 }
 
 pub async fn announce(announce: &str, params: &AnnounceParams) -> Result<AnnounceResponse, AnnounceError> {
+    let scheme = Url::parse(announce)
+        .map(|url| url.scheme().to_string())
+        .unwrap_or_default();
+
+    match scheme.as_str() {
+        "udp" => announce_udp(announce, params).await,
+        _ => announce_http(announce, params).await,
+    }
+}
+
+async fn announce_http(announce: &str, params: &AnnounceParams) -> Result<AnnounceResponse, AnnounceError> {
     // Reqwest does not support encoding the info hash as bytes so we encode it manually.
 
     let params_without_info_hash = [
@@ -107,33 +150,221 @@ pub async fn announce(announce: &str, params: &AnnounceParams) -> Result<Announc
             })?
         );
 
-    let peers = response_dict.get("peers")
-        .and_then(|peer_values| peer_values.as_list())
-        .ok_or(AnnounceError::MissingField("peers"))?
-        .iter()
-        .map(|peer_value| {
-            let peer_dict = peer_value.as_dictionary()
-                .ok_or(AnnounceError::MissingField("peer"))?;
-
-            let id = peer_dict.get("peer id")
-                .and_then(|id| id.as_str())
-                .ok_or(AnnounceError::MissingField("peer id"))?
-                .to_string();
-
-            let ip = peer_dict.get("ip")
-                .and_then(|ip| ip.as_str())
-                .ok_or(AnnounceError::MissingField("ip"))?
-                .to_string();
-
-            let port = peer_dict.get("port")
-                .and_then(|port| port.as_integer())
-                .ok_or(AnnounceError::MissingField("port"))?
-                .clone() as u16;
-
-            Ok(Peer { id, ip, port })
+    let peers_node = response_dict.get("peers")
+        .ok_or(AnnounceError::MissingField("peers"))?;
+
+    let peers = match &peers_node.value {
+        bencode::Value::List(peer_values) => peer_values
+            .iter()
+            .map(|peer_value| {
+                let peer_dict = peer_value.as_dictionary()
+                    .ok_or(AnnounceError::MissingField("peer"))?;
+
+                let id = peer_dict.get("peer id")
+                    .and_then(|id| id.as_str())
+                    .ok_or(AnnounceError::MissingField("peer id"))?
+                    .to_string();
+
+                let ip = peer_dict.get("ip")
+                    .and_then(|ip| ip.as_str())
+                    .ok_or(AnnounceError::MissingField("ip"))?
+                    .to_string();
+
+                let port = peer_dict.get("port")
+                    .and_then(|port| port.as_integer())
+                    .ok_or(AnnounceError::MissingField("port"))?
+                    .clone() as u16;
+
+                Ok(Peer { id, ip, port })
+            })
+            .collect::<Result<Vec<Peer>, AnnounceError>>()?,
+        bencode::Value::ByteString(peers_bytes) => parse_compact_peers(peers_bytes)?,
+        _ => return Err(AnnounceError::MissingField("peers")),
+    };
+
+    Ok(AnnounceResponse { interval, peers })
+}
+
+// Shared by the HTTP tracker's "peers" byte string and the UDP tracker's
+// announce response, both of which encode peers the same way (BEP 23).
+// Compact peer IDs aren't available from either wire format, so `Peer::id`
+// is always empty here.
+fn parse_compact_peers(peers_bytes: &[u8]) -> Result<Vec<Peer>, AnnounceError> {
+    if peers_bytes.len() % COMPACT_PEER_SIZE != 0 {
+        return Err(AnnounceError::InvalidPeers);
+    }
+
+    Ok(peers_bytes
+        .chunks(COMPACT_PEER_SIZE)
+        .map(|chunk| Peer {
+            id: String::new(),
+            ip: format!("{}.{}.{}.{}", chunk[0], chunk[1], chunk[2], chunk[3]),
+            port: u16::from_be_bytes([chunk[4], chunk[5]]),
         })
-    .collect::<Result<Vec<Peer>, AnnounceError>>()?;
+        .collect())
+}
+
+async fn announce_udp(announce: &str, params: &AnnounceParams) -> Result<AnnounceResponse, AnnounceError> {
+    let url = Url::parse(announce)
+        .ok()
+        .filter(|url| url.scheme() == "udp")
+        .ok_or_else(|| AnnounceError::UnsupportedScheme(announce.to_string()))?;
+    let host = url.host_str()
+        .ok_or_else(|| AnnounceError::UnsupportedScheme(announce.to_string()))?;
+    let port = url.port().ok_or_else(|| AnnounceError::UnsupportedScheme(announce.to_string()))?;
+
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect((host, port)).await?;
+
+    let connection_id = udp_connect(&socket).await?;
+
+    let mut rng = rand::thread_rng();
+    let transaction_id: u32 = rng.gen();
+    let key: u32 = rng.gen();
+
+    let event: u32 = match params.event {
+        AnnounceEvent::Completed => 1,
+        AnnounceEvent::Started => 2,
+        AnnounceEvent::Stopped => 3,
+    };
+
+    let mut request = Vec::with_capacity(98);
+    request.extend_from_slice(&connection_id.to_be_bytes());
+    request.extend_from_slice(&UDP_ACTION_ANNOUNCE.to_be_bytes());
+    request.extend_from_slice(&transaction_id.to_be_bytes());
+    request.extend_from_slice(&params.info_hash);
+    request.extend_from_slice(params.peer_id.as_bytes());
+    request.extend_from_slice(&(params.downloaded as u64).to_be_bytes());
+    request.extend_from_slice(&(params.left as u64).to_be_bytes());
+    request.extend_from_slice(&(params.uploaded as u64).to_be_bytes());
+    request.extend_from_slice(&event.to_be_bytes());
+    request.extend_from_slice(&0u32.to_be_bytes()); // ip: let the tracker use the source address.
+    request.extend_from_slice(&key.to_be_bytes());
+    request.extend_from_slice(&(-1i32).to_be_bytes()); // num_want: let the tracker decide.
+    request.extend_from_slice(&params.port.to_be_bytes());
+
+    let mut response = vec![0u8; 1500];
+    let response_len = send_and_receive(&socket, &request, &mut response).await?;
+
+    if response_len < 20 {
+        return Err(AnnounceError::InvalidUdpResponse);
+    }
+
+    let action = u32::from_be_bytes(response[0..4].try_into().unwrap());
+    let response_transaction_id = u32::from_be_bytes(response[4..8].try_into().unwrap());
+
+    if response_transaction_id != transaction_id {
+        return Err(AnnounceError::TransactionIdMismatch);
+    }
+
+    if action == UDP_ACTION_ERROR {
+        let message = str::from_utf8(&response[8..response_len]).unwrap_or("").to_string();
+        return Err(AnnounceError::ErrorResponse(message));
+    }
+
+    if action != UDP_ACTION_ANNOUNCE {
+        return Err(AnnounceError::InvalidUdpResponse);
+    }
+
+    let interval = Duration::from_secs(u32::from_be_bytes(response[8..12].try_into().unwrap()) as u64);
+    // response[12..16] is the leecher count, response[16..20] the seeder count;
+    // neither is surfaced on AnnounceResponse today.
+
+    let peers = parse_compact_peers(&response[20..response_len])?;
 
     Ok(AnnounceResponse { interval, peers })
 }
 
+async fn udp_connect(socket: &UdpSocket) -> Result<u64, AnnounceError> {
+    let transaction_id: u32 = rand::thread_rng().gen();
+
+    let mut request = Vec::with_capacity(16);
+    request.extend_from_slice(&UDP_PROTOCOL_ID.to_be_bytes());
+    request.extend_from_slice(&UDP_ACTION_CONNECT.to_be_bytes());
+    request.extend_from_slice(&transaction_id.to_be_bytes());
+
+    let mut response = [0u8; 16];
+    let response_len = send_and_receive(socket, &request, &mut response).await?;
+
+    if response_len < 16 {
+        return Err(AnnounceError::InvalidUdpResponse);
+    }
+
+    let action = u32::from_be_bytes(response[0..4].try_into().unwrap());
+    let response_transaction_id = u32::from_be_bytes(response[4..8].try_into().unwrap());
+
+    if response_transaction_id != transaction_id {
+        return Err(AnnounceError::TransactionIdMismatch);
+    }
+
+    if action == UDP_ACTION_ERROR {
+        let message = str::from_utf8(&response[8..response_len]).unwrap_or("").to_string();
+        return Err(AnnounceError::ErrorResponse(message));
+    }
+
+    if action != UDP_ACTION_CONNECT {
+        return Err(AnnounceError::InvalidUdpResponse);
+    }
+
+    Ok(u64::from_be_bytes(response[8..16].try_into().unwrap()))
+}
+
+// UDP is unreliable, so every request/response pair is wrapped in a
+// retransmit loop per BEP 15: resend with an exponentially growing timeout
+// (15 * 2^n seconds) until a response arrives or we give up after
+// `UDP_MAX_RETRIES` tries.
+async fn send_and_receive(socket: &UdpSocket, request: &[u8], response: &mut [u8]) -> Result<usize, AnnounceError> {
+    for attempt in 0..UDP_MAX_RETRIES {
+        socket.send(request).await?;
+
+        let attempt_timeout = UDP_BASE_TIMEOUT * 2u32.pow(attempt);
+
+        if let Ok(result) = timeout(attempt_timeout, socket.recv(response)).await {
+            return Ok(result?);
+        }
+    }
+
+    Err(AnnounceError::Timeout(UDP_MAX_RETRIES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_compact_peers_rejects_a_length_not_a_multiple_of_six() {
+        let result = parse_compact_peers(&[0u8; 7]);
+
+        assert!(matches!(result, Err(AnnounceError::InvalidPeers)));
+    }
+
+    #[test]
+    fn parse_compact_peers_decodes_ip_and_port() {
+        let peers = parse_compact_peers(&[192, 168, 1, 1, 0x1a, 0xe1]).unwrap();
+
+        assert_eq!(peers, vec![Peer { id: String::new(), ip: String::from("192.168.1.1"), port: 6881 }]);
+    }
+
+    #[test]
+    fn parse_compact_peers_decodes_multiple_peers_in_order() {
+        let bytes = [
+            192, 168, 1, 1, 0x1a, 0xe1,
+            10, 0, 0, 1, 0x1a, 0xe2,
+        ];
+
+        let peers = parse_compact_peers(&bytes).unwrap();
+
+        assert_eq!(peers, vec![
+            Peer { id: String::new(), ip: String::from("192.168.1.1"), port: 6881 },
+            Peer { id: String::new(), ip: String::from("10.0.0.1"), port: 6882 },
+        ]);
+    }
+
+    #[test]
+    fn parse_compact_peers_empty_bytes_is_an_empty_list() {
+        let peers = parse_compact_peers(&[]).unwrap();
+
+        assert!(peers.is_empty());
+    }
+}
+